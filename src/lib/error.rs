@@ -0,0 +1,25 @@
+//! Typed errors for the on-disk format, as opposed to the ad-hoc
+//! `anyhow::Error` used elsewhere for I/O and internal invariants. Being
+//! typed lets callers match on `FormatError::UnsupportedVersion` to
+//! drive a migration instead of just surfacing a message.
+
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum FormatError {
+    #[error("not a burqdb file: bad magic bytes")]
+    BadMagic,
+
+    #[error("unsupported format version {found}, this build supports {supported}")]
+    UnsupportedVersion { found: u32, supported: u32 },
+
+    #[error("header checksum mismatch: stored {stored:#010x}, computed {computed:#010x}")]
+    HeaderChecksumMismatch { stored: u32, computed: u32 },
+
+    #[error("page {page_id} checksum mismatch: stored {stored:#010x}, computed {computed:#010x}")]
+    PageChecksumMismatch {
+        page_id: usize,
+        stored: u32,
+        computed: u32,
+    },
+}
@@ -0,0 +1,290 @@
+//! # Buffer Pool
+//!
+//! Sits between `DbFile` and the upper storage layers (`BTree`,
+//! `SlottedPage`) so that hot pages can be reused across traversals
+//! instead of hitting the OS file on every access. Frames live in a
+//! fixed-size array of slots keyed by `page_id` through a `HashMap`
+//! page table; eviction is a clock-sweep (second-chance) policy over
+//! the slots that are not currently pinned.
+
+use std::collections::HashMap;
+
+use anyhow::{anyhow, bail, Result};
+
+use crate::io::file::DbFile;
+use crate::memory::Frame;
+
+struct Slot {
+    page_id: Option<usize>,
+    frame: Frame,
+    pinned: u32,
+    dirty: bool,
+    ref_bit: bool,
+}
+
+impl Slot {
+    fn empty(page_size: usize) -> Self {
+        Slot {
+            page_id: None,
+            frame: Frame::new(page_size),
+            pinned: 0,
+            dirty: false,
+            ref_bit: false,
+        }
+    }
+}
+
+pub struct BufferPool {
+    file: DbFile,
+    slots: Vec<Slot>,
+    page_table: HashMap<usize, usize>,
+    clock_hand: usize,
+}
+
+impl BufferPool {
+    pub fn new(file: DbFile, capacity: usize, page_size: usize) -> Self {
+        BufferPool {
+            file,
+            slots: (0..capacity).map(|_| Slot::empty(page_size)).collect(),
+            page_table: HashMap::new(),
+            clock_hand: 0,
+        }
+    }
+
+    /// Loads `page_id` into the pool if it is not already resident, pins it
+    /// and returns the slot holding it. Use `frame`/`frame_mut` to access
+    /// the underlying bytes and `unpin_page` once the caller is done.
+    pub fn fetch_page(&mut self, page_id: usize) -> Result<usize> {
+        if let Some(&slot_index) = self.page_table.get(&page_id) {
+            let slot = &mut self.slots[slot_index];
+            slot.pinned += 1;
+            slot.ref_bit = true;
+            return Ok(slot_index);
+        }
+
+        let slot_index = self.find_victim()?;
+
+        if let Some(old_page_id) = self.slots[slot_index].page_id {
+            self.page_table.remove(&old_page_id);
+        }
+
+        let frame = self.file.read_page(page_id)?;
+        let slot = &mut self.slots[slot_index];
+        slot.page_id = Some(page_id);
+        slot.frame = frame;
+        slot.pinned = 1;
+        slot.dirty = false;
+        slot.ref_bit = true;
+
+        self.page_table.insert(page_id, slot_index);
+        Ok(slot_index)
+    }
+
+    /// The backing file, for callers that need to go around the cache
+    /// entirely (e.g. reading/writing the superblock, which lives
+    /// outside the page array).
+    pub fn file(&self) -> &DbFile {
+        &self.file
+    }
+
+    pub fn frame(&self, slot_index: usize) -> &Frame {
+        &self.slots[slot_index].frame
+    }
+
+    pub fn frame_mut(&mut self, slot_index: usize) -> &mut Frame {
+        &mut self.slots[slot_index].frame
+    }
+
+    /// Unpins `page_id`. `is_dirty` marks the frame for write-back on
+    /// eviction or `flush_all`; it is sticky, so unpinning a clean reader
+    /// after a writer does not clear a dirty flag set earlier.
+    pub fn unpin_page(&mut self, page_id: usize, is_dirty: bool) -> Result<()> {
+        let slot_index = *self
+            .page_table
+            .get(&page_id)
+            .ok_or_else(|| anyhow!("page {} is not resident in the buffer pool", page_id))?;
+
+        let slot = &mut self.slots[slot_index];
+        if slot.pinned == 0 {
+            bail!("page {} is not pinned", page_id);
+        }
+        slot.pinned -= 1;
+        slot.dirty = slot.dirty || is_dirty;
+
+        Ok(())
+    }
+
+    /// Writes every dirty, resident frame back to `DbFile`.
+    pub fn flush_all(&mut self) -> Result<()> {
+        for slot in self.slots.iter_mut() {
+            if !slot.dirty {
+                continue;
+            }
+            if let Some(page_id) = slot.page_id {
+                self.file.write_page(
+                    page_id,
+                    slot.frame.as_bytes().to_vec().into_boxed_slice(),
+                    slot.frame.lsn(),
+                )?;
+                slot.dirty = false;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Scans the clock hand looking for an unpinned frame, clearing ref
+    /// bits along the way, and flushes the chosen victim if it is dirty.
+    fn find_victim(&mut self) -> Result<usize> {
+        if let Some(index) = self.slots.iter().position(|slot| slot.page_id.is_none()) {
+            return Ok(index);
+        }
+
+        let len = self.slots.len();
+        for _ in 0..(2 * len) {
+            let index = self.clock_hand;
+            self.clock_hand = (self.clock_hand + 1) % len;
+
+            let slot = &mut self.slots[index];
+            if slot.pinned > 0 {
+                continue;
+            }
+            if slot.ref_bit {
+                slot.ref_bit = false;
+                continue;
+            }
+
+            if slot.dirty {
+                if let Some(page_id) = slot.page_id {
+                    let bytes = slot.frame.as_bytes().to_vec().into_boxed_slice();
+                    self.file.write_page(page_id, bytes, slot.frame.lsn())?;
+                }
+            }
+
+            return Ok(index);
+        }
+
+        bail!("buffer pool exhausted: every frame is pinned")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::fs::OpenOptions;
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::io::file::PAGE_BUDGET;
+    use crate::io::page_codec::Codec;
+
+    /// A fresh `BufferPool` of the given `capacity`, backed by a temp
+    /// file with `page_count` pages already on disk, each filled with
+    /// its own page id as a byte so tests can tell pages apart.
+    fn temp_pool(name: &str, capacity: usize, page_count: usize) -> (BufferPool, std::path::PathBuf) {
+        let path = std::env::temp_dir().join(format!(
+            "burqdb-pool-test-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .write(true)
+            .truncate(true)
+            .open(&path)
+            .unwrap();
+        let db_file = DbFile::new(Rc::new(RefCell::new(file)), false, Codec::None);
+
+        for page_id in 0..page_count {
+            db_file
+                .write_page(page_id, vec![page_id as u8; PAGE_BUDGET].into_boxed_slice(), 0)
+                .unwrap();
+        }
+
+        (BufferPool::new(db_file, capacity, PAGE_BUDGET), path)
+    }
+
+    #[test]
+    fn fetch_page_returns_the_bytes_written_to_disk() {
+        let (mut pool, path) = temp_pool("fetch", 4, 2);
+
+        let slot = pool.fetch_page(1).unwrap();
+        assert_eq!(pool.frame(slot).as_bytes(), &[1u8; PAGE_BUDGET][..]);
+        pool.unpin_page(1, false).unwrap();
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn fetching_a_resident_page_twice_reuses_the_slot_and_requires_two_unpins() {
+        let (mut pool, path) = temp_pool("double-fetch", 4, 1);
+
+        let first = pool.fetch_page(0).unwrap();
+        let second = pool.fetch_page(0).unwrap();
+        assert_eq!(first, second);
+
+        pool.unpin_page(0, false).unwrap();
+        pool.unpin_page(0, false).unwrap();
+        assert!(pool.unpin_page(0, false).is_err());
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn unpinning_a_page_that_was_never_fetched_errors() {
+        let (mut pool, path) = temp_pool("unpin-miss", 4, 1);
+
+        assert!(pool.unpin_page(0, false).is_err());
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn a_pinned_page_is_never_chosen_as_an_eviction_victim() {
+        let (mut pool, path) = temp_pool("pin-protect", 1, 2);
+
+        pool.fetch_page(0).unwrap(); // left pinned
+
+        // The only slot is pinned and the pool is at capacity, so there
+        // is nowhere to evict to.
+        assert!(pool.fetch_page(1).is_err());
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn evicting_a_dirty_page_flushes_it_to_disk() {
+        let (mut pool, path) = temp_pool("evict-flush", 1, 2);
+
+        let slot = pool.fetch_page(0).unwrap();
+        pool.frame_mut(slot).as_bytes_mut().fill(0xAB);
+        pool.unpin_page(0, true).unwrap();
+
+        // Capacity is 1, so fetching page 1 must evict page 0's slot,
+        // and the eviction path is the only thing that can have
+        // persisted the dirty write above.
+        pool.fetch_page(1).unwrap();
+
+        let frame = pool.file().read_page(0).unwrap();
+        assert_eq!(frame.as_bytes(), &[0xABu8; PAGE_BUDGET][..]);
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn flush_all_persists_dirty_frames_without_evicting_them() {
+        let (mut pool, path) = temp_pool("flush-all", 4, 1);
+
+        let slot = pool.fetch_page(0).unwrap();
+        pool.frame_mut(slot).as_bytes_mut().fill(0xCD);
+        pool.unpin_page(0, true).unwrap();
+
+        pool.flush_all().unwrap();
+
+        let frame = pool.file().read_page(0).unwrap();
+        assert_eq!(frame.as_bytes(), &[0xCDu8; PAGE_BUDGET][..]);
+
+        let _ = std::fs::remove_file(path);
+    }
+}
@@ -0,0 +1,35 @@
+//! Minimal CRC-32 (IEEE 802.3) so the WAL and the on-disk page/header
+//! format can share one integrity check instead of each rolling their own.
+
+use std::sync::OnceLock;
+
+const POLY: u32 = 0xEDB88320;
+
+fn table() -> &'static [u32; 256] {
+    static TABLE: OnceLock<[u32; 256]> = OnceLock::new();
+    TABLE.get_or_init(|| {
+        let mut table = [0u32; 256];
+        for (i, slot) in table.iter_mut().enumerate() {
+            let mut crc = i as u32;
+            for _ in 0..8 {
+                crc = if crc & 1 != 0 {
+                    (crc >> 1) ^ POLY
+                } else {
+                    crc >> 1
+                };
+            }
+            *slot = crc;
+        }
+        table
+    })
+}
+
+pub fn crc32(bytes: &[u8]) -> u32 {
+    let table = table();
+    let mut crc = 0xFFFFFFFFu32;
+    for &byte in bytes {
+        let index = ((crc ^ byte as u32) & 0xFF) as usize;
+        crc = (crc >> 8) ^ table[index];
+    }
+    !crc
+}
@@ -1,21 +1,230 @@
+/// An in-memory page. Carries the LSN of the write-ahead log record it
+/// was last written through (or 0 if it was never written via the WAL),
+/// so recovery can tell whether a given log record is already reflected
+/// on disk without having to replay it again.
 pub struct Frame {
     mem: Box<[u8]>,
+    lsn: u64,
 }
 
 impl Frame {
     pub fn new(page_size: usize) -> Self {
         Frame {
             mem: vec![0; page_size].into_boxed_slice(),
+            lsn: 0,
         }
     }
 
-    pub fn from_bytes(buffer: Box<[u8]>) -> Self {
-        Frame { mem: buffer }
+    pub fn from_bytes(buffer: Box<[u8]>, lsn: u64) -> Self {
+        Frame { mem: buffer, lsn }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.mem
+    }
+
+    pub fn as_bytes_mut(&mut self) -> &mut [u8] {
+        &mut self.mem
+    }
+
+    pub fn lsn(&self) -> u64 {
+        self.lsn
+    }
+
+    pub fn set_lsn(&mut self, lsn: u64) {
+        self.lsn = lsn;
     }
 }
 
-static MAGIC_STR: &str = "burqdb";
-// Other details will be added later
+use crate::error::FormatError;
+use crate::io::crc32::crc32;
+
+pub const MAGIC: [u8; 6] = *b"burqdb";
+pub const FORMAT_VERSION: u32 = 1;
+
+/// Sentinel `free_list_head`/next-pointer value meaning "no page",
+/// since page ids start at 0 and so can't use 0 as that sentinel.
+pub const NIL_PAGE: u64 = u64::MAX;
+
+// magic(6) + format_version(4) + page_size(4) + page_count(8) +
+// free_list_head(8) + root_page_id(8) + last_lsn(8) + header_crc(4)
+pub const HEADER_LEN: usize = 6 + 4 + 4 + 8 + 8 + 8 + 8 + 4;
+
+/// The superblock persisted at file offset 0. Unlike a page, it is
+/// fixed-size and always read/written whole.
 pub struct DBHeader {
-    magic_string: &'static str,
+    pub magic: [u8; 6],
+    pub format_version: u32,
+    pub page_size: u32,
+    pub page_count: u64,
+    pub free_list_head: u64,
+    pub root_page_id: u64,
+    pub last_lsn: u64,
+}
+
+impl DBHeader {
+    pub fn new(page_size: u32) -> Self {
+        DBHeader {
+            magic: MAGIC,
+            format_version: FORMAT_VERSION,
+            page_size,
+            page_count: 0,
+            free_list_head: NIL_PAGE,
+            root_page_id: 0,
+            last_lsn: 0,
+        }
+    }
+
+    /// Serializes the header, appending a CRC32 over the fields above it.
+    pub fn to_bytes(&self) -> [u8; HEADER_LEN] {
+        let mut buffer = [0u8; HEADER_LEN];
+        let mut offset = 0;
+
+        buffer[offset..offset + 6].copy_from_slice(&self.magic);
+        offset += 6;
+        buffer[offset..offset + 4].copy_from_slice(&self.format_version.to_le_bytes());
+        offset += 4;
+        buffer[offset..offset + 4].copy_from_slice(&self.page_size.to_le_bytes());
+        offset += 4;
+        buffer[offset..offset + 8].copy_from_slice(&self.page_count.to_le_bytes());
+        offset += 8;
+        buffer[offset..offset + 8].copy_from_slice(&self.free_list_head.to_le_bytes());
+        offset += 8;
+        buffer[offset..offset + 8].copy_from_slice(&self.root_page_id.to_le_bytes());
+        offset += 8;
+        buffer[offset..offset + 8].copy_from_slice(&self.last_lsn.to_le_bytes());
+        offset += 8;
+
+        let crc = crc32(&buffer[..offset]);
+        buffer[offset..offset + 4].copy_from_slice(&crc.to_le_bytes());
+
+        buffer
+    }
+
+    /// Parses and validates a header read from disk: magic, format
+    /// version, and the trailing CRC must all check out.
+    pub fn from_bytes(buffer: &[u8]) -> Result<Self, FormatError> {
+        if buffer.len() < HEADER_LEN {
+            return Err(FormatError::BadMagic);
+        }
+
+        let mut offset = 0;
+        let magic: [u8; 6] = buffer[offset..offset + 6].try_into().unwrap();
+        if magic != MAGIC {
+            return Err(FormatError::BadMagic);
+        }
+        offset += 6;
+
+        let format_version = u32::from_le_bytes(buffer[offset..offset + 4].try_into().unwrap());
+        offset += 4;
+        if format_version != FORMAT_VERSION {
+            return Err(FormatError::UnsupportedVersion {
+                found: format_version,
+                supported: FORMAT_VERSION,
+            });
+        }
+
+        let page_size = u32::from_le_bytes(buffer[offset..offset + 4].try_into().unwrap());
+        offset += 4;
+        let page_count = u64::from_le_bytes(buffer[offset..offset + 8].try_into().unwrap());
+        offset += 8;
+        let free_list_head = u64::from_le_bytes(buffer[offset..offset + 8].try_into().unwrap());
+        offset += 8;
+        let root_page_id = u64::from_le_bytes(buffer[offset..offset + 8].try_into().unwrap());
+        offset += 8;
+        let last_lsn = u64::from_le_bytes(buffer[offset..offset + 8].try_into().unwrap());
+        offset += 8;
+
+        let stored_crc = u32::from_le_bytes(buffer[offset..offset + 4].try_into().unwrap());
+        let computed_crc = crc32(&buffer[..offset]);
+        if stored_crc != computed_crc {
+            return Err(FormatError::HeaderChecksumMismatch {
+                stored: stored_crc,
+                computed: computed_crc,
+            });
+        }
+
+        Ok(DBHeader {
+            magic,
+            format_version,
+            page_size,
+            page_count,
+            free_list_head,
+            root_page_id,
+            last_lsn,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frame_round_trips_bytes_and_lsn() {
+        let mut frame = Frame::new(16);
+        assert_eq!(frame.lsn(), 0);
+
+        frame.as_bytes_mut().copy_from_slice(&[1u8; 16]);
+        frame.set_lsn(9);
+        assert_eq!(frame.as_bytes(), &[1u8; 16]);
+        assert_eq!(frame.lsn(), 9);
+
+        let rebuilt = Frame::from_bytes(vec![2u8; 16].into_boxed_slice(), 3);
+        assert_eq!(rebuilt.as_bytes(), &[2u8; 16]);
+        assert_eq!(rebuilt.lsn(), 3);
+    }
+
+    #[test]
+    fn header_round_trips_through_bytes() {
+        let mut header = DBHeader::new(4096);
+        header.page_count = 5;
+        header.free_list_head = 2;
+        header.root_page_id = 1;
+        header.last_lsn = 7;
+
+        let bytes = header.to_bytes();
+        let decoded = DBHeader::from_bytes(&bytes).unwrap();
+
+        assert_eq!(decoded.magic, MAGIC);
+        assert_eq!(decoded.page_count, 5);
+        assert_eq!(decoded.free_list_head, 2);
+        assert_eq!(decoded.root_page_id, 1);
+        assert_eq!(decoded.last_lsn, 7);
+    }
+
+    #[test]
+    fn header_from_bytes_rejects_bad_magic() {
+        let mut bytes = DBHeader::new(4096).to_bytes();
+        bytes[0] = b'X';
+
+        assert!(matches!(
+            DBHeader::from_bytes(&bytes),
+            Err(FormatError::BadMagic)
+        ));
+    }
+
+    #[test]
+    fn header_from_bytes_rejects_corrupted_crc() {
+        let mut bytes = DBHeader::new(4096).to_bytes();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0xff;
+
+        assert!(matches!(
+            DBHeader::from_bytes(&bytes),
+            Err(FormatError::HeaderChecksumMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn header_from_bytes_rejects_unsupported_version() {
+        let mut header = DBHeader::new(4096);
+        header.format_version = FORMAT_VERSION + 1;
+        let bytes = header.to_bytes();
+
+        assert!(matches!(
+            DBHeader::from_bytes(&bytes),
+            Err(FormatError::UnsupportedVersion { .. })
+        ));
+    }
 }
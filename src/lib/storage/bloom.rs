@@ -0,0 +1,148 @@
+//! # Bloom filter sidecar
+//!
+//! A per-file Bloom filter so `BTree::get` can short-circuit a miss
+//! without a full tree descent, the same filter-block idea LSM/SSTable
+//! engines use to skip irrelevant files on a point lookup. The `k` probe
+//! positions come from double hashing two 64-bit hashes of the key
+//! (`h_i = h1 + i*h2`) rather than running `k` independent hash
+//! functions.
+
+use std::collections::hash_map::DefaultHasher;
+use std::f64::consts::LN_2;
+use std::hash::{Hash, Hasher};
+
+use anyhow::{bail, Result};
+use bincode::{
+    config::standard,
+    serde::{decode_from_slice, encode_to_vec},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::io::file::PAGE_BUDGET;
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct BloomFilter {
+    bits: Vec<u8>,
+    num_bits: usize,
+    num_hashes: u32,
+}
+
+impl BloomFilter {
+    /// Sizes the filter for `expected_items` keys at a target
+    /// `false_positive_rate`, following the standard
+    /// `m = -n*ln(p)/(ln2)^2`, `k = round((m/n)*ln2)` formulas.
+    pub fn with_capacity(expected_items: usize, false_positive_rate: f64) -> Self {
+        let n = expected_items.max(1) as f64;
+        let m = (-n * false_positive_rate.ln() / (LN_2 * LN_2)).ceil() as usize;
+        let num_bits = m.max(8);
+        let num_hashes = ((num_bits as f64 / n) * LN_2).round().max(1.0) as u32;
+
+        BloomFilter {
+            bits: vec![0u8; num_bits.div_ceil(8)],
+            num_bits,
+            num_hashes,
+        }
+    }
+
+    fn seed_hashes(key: &str) -> (u64, u64) {
+        let mut h1 = DefaultHasher::new();
+        key.hash(&mut h1);
+        let h1 = h1.finish();
+
+        let mut h2 = DefaultHasher::new();
+        key.hash(&mut h2);
+        0x9E37_79B9_7F4A_7C15u64.hash(&mut h2);
+        let h2 = h2.finish() | 1; // odd step: for an even num_bits, positions() can't degenerate to repeating one bit
+
+        (h1, h2)
+    }
+
+    fn positions(&self, key: &str) -> impl Iterator<Item = usize> {
+        let (h1, h2) = Self::seed_hashes(key);
+        let num_bits = self.num_bits as u64;
+        (0..self.num_hashes).map(move |i| (h1.wrapping_add((i as u64).wrapping_mul(h2)) % num_bits) as usize)
+    }
+
+    pub fn insert(&mut self, key: &str) {
+        for position in self.positions(key).collect::<Vec<_>>() {
+            self.bits[position / 8] |= 1 << (position % 8);
+        }
+    }
+
+    /// `false` means `key` is *definitely* absent; `true` means it
+    /// might be present — a false positive is possible, a false
+    /// negative is not.
+    pub fn may_contain(&self, key: &str) -> bool {
+        self.positions(key)
+            .all(|position| self.bits[position / 8] & (1 << (position % 8)) != 0)
+    }
+
+    pub fn to_bytes(&self) -> Result<Box<[u8]>> {
+        let mut bytes = encode_to_vec(self, standard())?;
+        if bytes.len() > PAGE_BUDGET {
+            bail!(
+                "bloom filter needs {} bytes, which does not fit in the {} byte budget",
+                bytes.len(),
+                PAGE_BUDGET
+            );
+        }
+        bytes.resize(PAGE_BUDGET, 0);
+        Ok(bytes.into_boxed_slice())
+    }
+
+    pub fn from_bytes(buffer: &[u8]) -> Result<Self> {
+        let (filter, _) = decode_from_slice(buffer, standard())?;
+        Ok(filter)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inserted_keys_are_always_reported_as_maybe_present() {
+        let mut filter = BloomFilter::with_capacity(100, 0.01);
+
+        for i in 0..100 {
+            filter.insert(&format!("key-{}", i));
+        }
+
+        for i in 0..100 {
+            assert!(filter.may_contain(&format!("key-{}", i)));
+        }
+    }
+
+    #[test]
+    fn false_positive_rate_stays_roughly_within_the_requested_bound() {
+        let mut filter = BloomFilter::with_capacity(1000, 0.01);
+        for i in 0..1000 {
+            filter.insert(&format!("present-{}", i));
+        }
+
+        let false_positives = (0..10_000)
+            .filter(|i| filter.may_contain(&format!("absent-{}", i)))
+            .count();
+
+        // A generous margin over the requested 1% — this is a sizing
+        // sanity check, not a tight statistical bound.
+        assert!(
+            false_positives < 500,
+            "saw {} false positives out of 10000",
+            false_positives
+        );
+    }
+
+    #[test]
+    fn round_trips_through_bytes() {
+        let mut filter = BloomFilter::with_capacity(100, 0.01);
+        filter.insert("a");
+        filter.insert("b");
+
+        let bytes = filter.to_bytes().unwrap();
+        let decoded = BloomFilter::from_bytes(&bytes).unwrap();
+
+        assert!(decoded.may_contain("a"));
+        assert!(decoded.may_contain("b"));
+    }
+}
@@ -1,54 +1,592 @@
+use std::cell::RefCell;
+use std::fs::OpenOptions;
+use std::path::Path;
+use std::rc::Rc;
 
+use anyhow::{Context, Result};
+
+use crate::io::buffer_pool::BufferPool;
+use crate::io::file::{DbFile, PAGE_BUDGET};
+use crate::io::page_codec::Codec;
+use crate::io::wal::Wal;
+use crate::memory::DBHeader;
+use crate::storage::bloom::BloomFilter;
+use crate::storage::cell::Cell;
+use crate::storage::free_space::FreeSpaceManager;
+use crate::storage::slotted_page::{IndexPage, Node, SlottedPage};
+
+// Page 0 is always the root leaf of a freshly created file; page 1 is
+// always the Bloom filter sidecar. Both are bootstrapped together in
+// `load`. The Bloom page never moves, so `BTree` never needs to look it
+// up; the root moves once it splits and is tracked in
+// `header.root_page_id`.
+const BLOOM_PAGE_ID: u64 = 1;
+const BLOOM_EXPECTED_KEYS: usize = 2048;
+const BLOOM_FALSE_POSITIVE_RATE: f64 = 0.01;
+
+// How many frames the buffer pool keeps resident. Node reads/writes,
+// Bloom sidecar saves and free-list updates go through it, in `RefCell`
+// so the read-only public API (`get`, `range_query`) can still borrow
+// `self` immutably. The bootstrap in `load`, the superblock and freshly
+// grown pages are read/written on the file directly.
+const POOL_CAPACITY: usize = 64;
 
 pub struct BTree {
-    // pub root_node: Option<Box<BTreeNode>>,
+    pool: RefCell<BufferPool>,
+    wal: RefCell<Wal>,
+    header: DBHeader,
+    bloom: BloomFilter,
 }
 
-
 impl BTree {
-    pub fn new() -> Self {
-        BTree {}
+    /// Opens the db file at `path`, validating its superblock, replaying
+    /// any log left behind by a crash, and bootstraps an empty root leaf
+    /// and Bloom filter the first time the file is used. `codec` is the
+    /// per-page compression newly written pages are stored with;
+    /// existing pages decompress by whichever codec their trailer names,
+    /// regardless of what is passed here.
+    pub fn load(path: &str, codec: Codec) -> Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(path)
+            .with_context(|| format!("failed to open db file at {}", path))?;
+        let db = DbFile::new(Rc::new(RefCell::new(file)), false, codec);
+        let pool = RefCell::new(BufferPool::new(db, POOL_CAPACITY, PAGE_BUDGET));
+
+        let mut wal = Wal::open(&Path::new(path).with_extension("wal"))
+            .with_context(|| format!("failed to open write-ahead log for {}", path))?;
+        let db_file = pool.borrow();
+        wal.recover(db_file.file(), |page_id| {
+            db_file.file().page_lsn(page_id as usize).unwrap_or(0)
+        })
+        .with_context(|| format!("failed to replay write-ahead log for {}", path))?;
+        drop(db_file);
+        wal.checkpoint()?;
+
+        let mut header = pool
+            .borrow()
+            .file()
+            .read_header()
+            .with_context(|| format!("invalid db header at {}", path))?;
+
+        let bloom = if header.page_count == 0 {
+            let root: Node<String, String> = Node::Leaf(SlottedPage::new(0));
+            pool.borrow().file().write_page(0, root.to_bytes()?, 0)?;
+
+            let bloom = BloomFilter::with_capacity(BLOOM_EXPECTED_KEYS, BLOOM_FALSE_POSITIVE_RATE);
+            pool.borrow()
+                .file()
+                .write_page(BLOOM_PAGE_ID as usize, bloom.to_bytes()?, 0)?;
+
+            header.page_count = 2;
+            header.root_page_id = 0;
+            pool.borrow().file().write_header(&header)?;
+
+            bloom
+        } else {
+            let frame = pool.borrow().file().read_page(BLOOM_PAGE_ID as usize)?;
+            BloomFilter::from_bytes(frame.as_bytes())?
+        };
+
+        Ok(BTree {
+            pool,
+            wal: RefCell::new(wal),
+            header,
+            bloom,
+        })
+    }
+
+    /// Flushes every dirty frame to `DbFile` and checkpoints the log —
+    /// once this returns, the write-ahead log is no longer needed to
+    /// recover anything written so far. Call this once at the end of a
+    /// top-level mutating operation, after all of its page writes.
+    fn sync(&self) -> Result<()> {
+        self.pool.borrow_mut().flush_all()?;
+        self.wal.borrow_mut().checkpoint()
+    }
+
+    fn save_bloom(&self) -> Result<()> {
+        let bytes = self.bloom.to_bytes()?;
+        self.put_page(BLOOM_PAGE_ID, bytes)
+    }
+
+    fn read_node(&self, page_id: u64) -> Result<Node<String, String>> {
+        let slot = self.pool.borrow_mut().fetch_page(page_id as usize)?;
+        let node = Node::from_bytes(self.pool.borrow().frame(slot).as_bytes());
+        self.pool.borrow_mut().unpin_page(page_id as usize, false)?;
+        node
+    }
+
+    fn write_node(&self, node: &Node<String, String>) -> Result<()> {
+        let bytes = node.to_bytes()?;
+        self.put_page(node.page_id(), bytes)
+    }
+
+    /// Appends the full page image to the write-ahead log before handing
+    /// it to the buffer pool, so a crash between the two is recoverable
+    /// on the next `load`.
+    fn put_page(&self, page_id: u64, bytes: Box<[u8]>) -> Result<()> {
+        let lsn = self.wal.borrow_mut().append(page_id, &bytes)?;
+
+        let slot = self.pool.borrow_mut().fetch_page(page_id as usize)?;
+        let mut pool = self.pool.borrow_mut();
+        let frame = pool.frame_mut(slot);
+        frame.as_bytes_mut().copy_from_slice(&bytes);
+        frame.set_lsn(lsn);
+        drop(pool);
+        self.pool.borrow_mut().unpin_page(page_id as usize, true)
     }
 
-    pub fn load(path: &str) -> Self {
-        println!("Loading B-tree from path: {}", path);
-        BTree {}
+    fn allocate_page(&mut self) -> Result<u64> {
+        let mut pool = self.pool.borrow_mut();
+        let page_id = FreeSpaceManager::new(&mut pool).allocate_page(&mut self.header)?;
+        drop(pool);
+        self.pool.borrow().file().write_header(&self.header)?;
+        Ok(page_id)
     }
 
-    pub fn insert(&self, key: String, value: String) {
-        // Insert key-value pair into the B-tree
-        println!("Inserting key: {}, value: {}", key, value);
+    /// Descends from the root, binary-searching separator keys at each
+    /// internal page, and returns the path of page ids from root to the
+    /// leaf that should hold `key`.
+    fn find_leaf(&self, key: &String) -> Result<Vec<u64>> {
+        let mut path = vec![self.header.root_page_id];
+
+        loop {
+            let current = *path.last().unwrap();
+            match self.read_node(current)? {
+                Node::Leaf(_) => break,
+                Node::Internal(index_page) => path.push(index_page.child_for(key)),
+            }
+        }
+
+        Ok(path)
+    }
+
+    pub fn insert(&mut self, key: String, value: String) -> Result<()> {
+        self.bloom.insert(&key);
+        self.save_bloom()?;
+
+        let mut path = self.find_leaf(&key)?;
+        let leaf_id = path.pop().unwrap();
+        let leaf = match self.read_node(leaf_id)? {
+            Node::Leaf(leaf) => leaf,
+            Node::Internal(_) => unreachable!("find_leaf always stops on a leaf"),
+        };
+
+        let cell = Cell::new(key, value)?;
+
+        let mut candidate = leaf.clone();
+        candidate.insert(cell.clone());
+
+        if Node::Leaf(candidate.clone()).fits()? {
+            self.write_node(&Node::Leaf(candidate))?;
+        } else {
+            self.split_leaf_and_insert(path, leaf, cell)?;
+        }
+
+        self.sync()
+    }
+
+    fn split_leaf_and_insert(
+        &mut self,
+        ancestors: Vec<u64>,
+        mut leaf: SlottedPage<String, String>,
+        cell: Cell<String, String>,
+    ) -> Result<()> {
+        let new_page_id = self.allocate_page()?;
+        let (mut sibling, separator_key) = leaf.split(new_page_id);
+
+        if cell.key() < &separator_key {
+            leaf.insert(cell);
+        } else {
+            sibling.insert(cell);
+        }
+
+        self.write_node(&Node::Leaf(leaf))?;
+        self.write_node(&Node::Leaf(sibling))?;
+
+        self.insert_into_parent(ancestors, separator_key, new_page_id)
+    }
+
+    /// Pushes `separator_key`/`new_child_id` into the parent named by the
+    /// last entry of `ancestors`, splitting it (recursively) if it is
+    /// full, or growing a new root if `ancestors` is empty.
+    fn insert_into_parent(
+        &mut self,
+        mut ancestors: Vec<u64>,
+        separator_key: String,
+        new_child_id: u64,
+    ) -> Result<()> {
+        let Some(parent_id) = ancestors.pop() else {
+            let new_root_id = self.allocate_page()?;
+            let mut new_root = IndexPage::new(new_root_id, self.header.root_page_id);
+            new_root.insert(separator_key, new_child_id);
+            self.write_node(&Node::Internal(new_root))?;
+
+            self.header.root_page_id = new_root_id;
+            self.pool.borrow().file().write_header(&self.header)?;
+            return Ok(());
+        };
+
+        let parent = match self.read_node(parent_id)? {
+            Node::Internal(parent) => parent,
+            Node::Leaf(_) => unreachable!("a leaf's ancestor is always internal"),
+        };
+
+        let mut candidate = parent.clone();
+        candidate.insert(separator_key.clone(), new_child_id);
+
+        if Node::<String, String>::Internal(candidate.clone()).fits()? {
+            self.write_node(&Node::Internal(candidate))
+        } else {
+            self.split_internal_and_insert(ancestors, parent, separator_key, new_child_id)
+        }
+    }
+
+    fn split_internal_and_insert(
+        &mut self,
+        ancestors: Vec<u64>,
+        mut parent: IndexPage<String>,
+        separator_key: String,
+        new_child_id: u64,
+    ) -> Result<()> {
+        let new_parent_id = self.allocate_page()?;
+        let (mut sibling, promoted_key) = parent.split(new_parent_id);
+
+        if separator_key < promoted_key {
+            parent.insert(separator_key, new_child_id);
+        } else {
+            sibling.insert(separator_key, new_child_id);
+        }
+
+        self.write_node(&Node::Internal(parent))?;
+        self.write_node(&Node::Internal(sibling))?;
+
+        self.insert_into_parent(ancestors, promoted_key, new_parent_id)
     }
 
     pub fn get(&self, key: &String) -> Option<String> {
-        // Retrieve value by key from the B-tree
-        println!("Getting value for key: {}", key);
-        None
+        if !self.bloom.may_contain(key) {
+            return None;
+        }
+
+        self.get_inner(key).ok().flatten()
     }
 
-    pub fn delete(&self, key: &String) {
-        // Delete key-value pair from the B-tree
-        println!("Deleting key: {}", key);
+    fn get_inner(&self, key: &String) -> Result<Option<String>> {
+        let path = self.find_leaf(key)?;
+        let leaf = match self.read_node(*path.last().unwrap())? {
+            Node::Leaf(leaf) => leaf,
+            Node::Internal(_) => unreachable!("find_leaf always stops on a leaf"),
+        };
+
+        Ok(leaf.find(key).map(|cell| cell.value().clone()))
+    }
+
+    pub fn delete(&mut self, key: &String) {
+        if let Err(err) = self.delete_inner(key) {
+            eprintln!("failed to delete key {}: {:#}", key, err);
+        }
+    }
+
+    fn delete_inner(&mut self, key: &String) -> Result<()> {
+        let mut path = self.find_leaf(key)?;
+        let leaf_id = path.pop().unwrap();
+        let mut leaf = match self.read_node(leaf_id)? {
+            Node::Leaf(leaf) => leaf,
+            Node::Internal(_) => unreachable!("find_leaf always stops on a leaf"),
+        };
+
+        leaf.remove(key);
+
+        // A leaf that is its parent's only child cannot be detached
+        // (the parent would be left with nothing to route to), so it is
+        // kept, empty, instead of freed.
+        let only_child = match path.last() {
+            Some(&parent_id) => match self.read_node(parent_id)? {
+                Node::Internal(parent) => parent.leftmost_child == leaf_id && parent.is_empty(),
+                Node::Leaf(_) => unreachable!("a leaf's ancestor is always internal"),
+            },
+            None => false,
+        };
+
+        if leaf.is_empty() && leaf_id != self.header.root_page_id && !only_child {
+            // `path` is now the ancestor chain (root..parent) above the
+            // emptied leaf. Detaching it — so no separator and no
+            // sibling `right` pointer still routes into it — has to
+            // happen before it is handed back out by `allocate_page`;
+            // this does not merge/rebalance the now-sparser internal
+            // nodes, only guarantees the freed leaf itself is
+            // unreachable.
+            self.detach_leaf(&path, leaf_id, leaf.right)?;
+            let mut pool = self.pool.borrow_mut();
+            FreeSpaceManager::new(&mut pool).free_page(&mut self.header, leaf_id)?;
+            drop(pool);
+            self.pool.borrow().file().write_header(&self.header)?;
+        } else {
+            self.write_node(&Node::Leaf(leaf))?;
+        }
+
+        self.sync()
+    }
+
+    /// Repoints whichever leaf's `right` points at `leaf_id` to
+    /// `leaf_right` instead, and removes `leaf_id`'s entry from its
+    /// parent (the last page id in `ancestors`), so nothing still
+    /// routes reads into `leaf_id` before it is freed.
+    fn detach_leaf(&mut self, ancestors: &[u64], leaf_id: u64, leaf_right: Option<u64>) -> Result<()> {
+        let first_leaf_id = self.leftmost_leaf_id()?;
+        if first_leaf_id != leaf_id {
+            let mut predecessor_id = first_leaf_id;
+            loop {
+                let mut predecessor = match self.read_node(predecessor_id)? {
+                    Node::Leaf(leaf) => leaf,
+                    Node::Internal(_) => unreachable!("the leaf chain only contains leaves"),
+                };
+
+                if predecessor.right == Some(leaf_id) {
+                    predecessor.right = leaf_right;
+                    self.write_node(&Node::Leaf(predecessor))?;
+                    break;
+                }
+
+                predecessor_id = predecessor
+                    .right
+                    .expect("leaf_id must appear somewhere in the chain");
+            }
+        }
+
+        if let Some(&parent_id) = ancestors.last() {
+            let mut parent = match self.read_node(parent_id)? {
+                Node::Internal(parent) => parent,
+                Node::Leaf(_) => unreachable!("a leaf's ancestor is always internal"),
+            };
+            parent.remove_child(leaf_id);
+            self.write_node(&Node::Internal(parent))?;
+        }
+
+        Ok(())
+    }
+
+    /// Descends from the root via `leftmost_child` to find the very
+    /// first leaf in the tree's left-to-right chain.
+    fn leftmost_leaf_id(&self) -> Result<u64> {
+        let mut current = self.header.root_page_id;
+        loop {
+            match self.read_node(current)? {
+                Node::Leaf(_) => return Ok(current),
+                Node::Internal(index_page) => current = index_page.leftmost_child,
+            }
+        }
     }
 
     pub fn range_query(&self, start: &String, end: &String) -> Vec<(String, String)> {
-        // Perform a range query from start to end keys
-        println!("Performing range query from {} to {}", start, end);
-        vec![]
+        self.range_query_filter(start, end, |_, _| true)
     }
 
+    /// Seeks to the leaf for `start` and walks `right` sibling pointers,
+    /// collecting cells whose key falls in `[start, end]` and passes
+    /// `filter`, until a key exceeds `end`.
+    pub fn range_query_filter<F>(
+        &self,
+        start: &String,
+        end: &String,
+        filter: F,
+    ) -> Vec<(String, String)>
+    where
+        F: Fn(&String, &String) -> bool,
+    {
+        self.range_query_inner(start, end, filter).unwrap_or_default()
+    }
 
-    pub fn range_query_filter<F>(&self, start: &String, end: &String, filter: F) -> Vec<(String, String)>
+    fn range_query_inner<F>(
+        &self,
+        start: &String,
+        end: &String,
+        filter: F,
+    ) -> Result<Vec<(String, String)>>
     where
         F: Fn(&String, &String) -> bool,
     {
-        // Perform a range query with a filter function
-        println!("Performing filtered range query from {} to {}", start, end);
-        vec![]
+        let path = self.find_leaf(start)?;
+        let mut next_leaf_id = Some(*path.last().unwrap());
+        let mut results = Vec::new();
+
+        while let Some(leaf_id) = next_leaf_id {
+            let leaf = match self.read_node(leaf_id)? {
+                Node::Leaf(leaf) => leaf,
+                Node::Internal(_) => unreachable!("a leaf's right sibling is always a leaf"),
+            };
+
+            for cell in leaf.cells() {
+                if cell.key() > end {
+                    return Ok(results);
+                }
+                if cell.key() >= start && filter(cell.key(), cell.value()) {
+                    results.push((cell.key().clone(), cell.value().clone()));
+                }
+            }
+
+            next_leaf_id = leaf.right;
+        }
+
+        Ok(results)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{self, OpenOptions};
+    use std::io::Write;
+
+    use crate::memory::DBHeader;
+
+    use super::*;
+
+    /// Creates a fresh, empty db file (just a bootstrap superblock, no
+    /// pages yet) at a unique path under the system temp dir and returns
+    /// a `BTree` loaded from it. `BTree::load` does not create the file
+    /// or its superblock, only the root leaf and Bloom page, so tests
+    /// seed the superblock themselves.
+    fn open_temp_btree(name: &str) -> (BTree, std::path::PathBuf) {
+        let path = std::env::temp_dir().join(format!(
+            "burqdb-btree-test-{}-{}-{}",
+            name,
+            std::process::id(),
+            name.len()
+        ));
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(&path)
+            .unwrap();
+        file.write_all(&DBHeader::new(PAGE_BUDGET as u32).to_bytes())
+            .unwrap();
+        drop(file);
+
+        let tree = BTree::load(path.to_str().unwrap(), Codec::None).unwrap();
+        (tree, path)
+    }
+
+    fn cleanup(path: &std::path::Path) {
+        let _ = fs::remove_file(path);
+        let _ = fs::remove_file(path.with_extension("wal"));
+    }
+
+    #[test]
+    fn insert_and_get_round_trip() {
+        let (mut tree, path) = open_temp_btree("insert-get");
+
+        tree.insert("a".to_string(), "1".to_string()).unwrap();
+        tree.insert("b".to_string(), "2".to_string()).unwrap();
+
+        assert_eq!(tree.get(&"a".to_string()), Some("1".to_string()));
+        assert_eq!(tree.get(&"b".to_string()), Some("2".to_string()));
+        assert_eq!(tree.get(&"missing".to_string()), None);
+
+        cleanup(&path);
+    }
+
+    #[test]
+    fn insert_forces_leaf_splits_and_grows_a_new_root() {
+        let (mut tree, path) = open_temp_btree("splits");
+
+        // Values are padded so a handful of cells overflow a leaf's page
+        // budget, forcing repeated leaf splits; the first one splits the
+        // root leaf and grows a new internal root above it.
+        let padding = "x".repeat(400);
+        for i in 0..40u32 {
+            let key = format!("key-{:04}", i);
+            tree.insert(key, padding.clone()).unwrap();
+        }
+
+        for i in 0..40u32 {
+            let key = format!("key-{:04}", i);
+            assert_eq!(tree.get(&key), Some(padding.clone()), "missing {}", key);
+        }
+
+        cleanup(&path);
     }
 
-    
+    #[test]
+    fn range_query_walks_the_leaf_chain_in_order() {
+        let (mut tree, path) = open_temp_btree("range");
 
-    
+        let padding = "x".repeat(400);
+        for i in 0..30u32 {
+            let key = format!("key-{:04}", i);
+            tree.insert(key, padding.clone()).unwrap();
+        }
+
+        let results = tree.range_query(&"key-0005".to_string(), &"key-0010".to_string());
+        let keys: Vec<&String> = results.iter().map(|(k, _)| k).collect();
+        assert_eq!(
+            keys,
+            vec![
+                "key-0005", "key-0006", "key-0007", "key-0008", "key-0009", "key-0010"
+            ]
+        );
+
+        cleanup(&path);
+    }
 
-}
\ No newline at end of file
+    #[test]
+    fn delete_removes_the_key() {
+        let (mut tree, path) = open_temp_btree("delete");
+
+        tree.insert("a".to_string(), "1".to_string()).unwrap();
+        tree.insert("b".to_string(), "2".to_string()).unwrap();
+        tree.delete(&"a".to_string());
+
+        assert_eq!(tree.get(&"a".to_string()), None);
+        assert_eq!(tree.get(&"b".to_string()), Some("2".to_string()));
+
+        cleanup(&path);
+    }
+
+    /// Regression test for the premature page-reuse bug: emptying and
+    /// freeing a leaf must detach it from both its parent separator and
+    /// the predecessor leaf's `right` pointer before the page id is
+    /// handed back out, or a later insert that reuses the id silently
+    /// corrupts whatever still routed reads into it.
+    #[test]
+    fn deleting_a_leaf_and_reusing_its_page_does_not_corrupt_the_chain() {
+        let (mut tree, path) = open_temp_btree("free-reuse");
+
+        let padding = "x".repeat(400);
+        for i in 0..60u32 {
+            let key = format!("key-{:04}", i);
+            tree.insert(key, padding.clone()).unwrap();
+        }
+
+        // Empty out an early leaf's worth of keys so its page is freed.
+        for i in 0..5u32 {
+            let key = format!("key-{:04}", i);
+            tree.delete(&key);
+        }
+
+        // Insert enough new keys to force the freed page id to be
+        // reallocated and reused for unrelated data.
+        for i in 60..90u32 {
+            let key = format!("key-{:04}", i);
+            tree.insert(key, padding.clone()).unwrap();
+        }
+
+        for i in 0..5u32 {
+            let key = format!("key-{:04}", i);
+            assert_eq!(tree.get(&key), None, "deleted key {} resurfaced", key);
+        }
+        for i in 5..90u32 {
+            let key = format!("key-{:04}", i);
+            assert_eq!(tree.get(&key), Some(padding.clone()), "missing {}", key);
+        }
+
+        let results = tree.range_query(&"key-0000".to_string(), &"key-0089".to_string());
+        assert_eq!(results.len(), 85);
+
+        cleanup(&path);
+    }
+}
@@ -7,7 +7,7 @@ use anyhow::Result;
 use bincode::{config::standard, serde::encode_to_vec};
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct Cell<K, V> {
     key: K,
     value: V,
@@ -29,6 +29,18 @@ where
         })
     }
 
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    pub fn value(&self) -> &V {
+        &self.value
+    }
+
+    pub fn into_parts(self) -> (K, V) {
+        (self.key, self.value)
+    }
+
     pub fn _serialize(&self) -> Result<Vec<u8>> {
         let key_bytes = encode_to_vec(&self.key, standard()).unwrap();
         let value_bytes = encode_to_vec(&self.value, standard()).unwrap();
@@ -44,7 +56,7 @@ where
     pub fn size(&mut self) -> usize {
         match self.cached_size {
             None => {
-                self.cached_size = Some(self._serialize().unwrap().len() as usize);
+                self.cached_size = Some(self._serialize().unwrap().len());
                 self.cached_size.unwrap()
             }
             Some(cached_size) => cached_size,
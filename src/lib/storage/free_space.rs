@@ -0,0 +1,157 @@
+//! # Free-space manager
+//!
+//! Reclaims pages once a split obsoletes a node or a delete empties a
+//! leaf, instead of letting the file grow unboundedly. Maintains an
+//! on-disk singly-linked free list rooted at `DBHeader::free_list_head`:
+//! each freed page stores the page id of the next free page in its
+//! first 8 bytes, with `NIL_PAGE` marking the end of the list (and,
+//! when it is also the head, an empty list).
+
+use anyhow::Result;
+
+use crate::io::buffer_pool::BufferPool;
+use crate::io::file::PAGE_BUDGET;
+use crate::memory::{DBHeader, NIL_PAGE};
+
+pub struct FreeSpaceManager<'a> {
+    pool: &'a mut BufferPool,
+}
+
+impl<'a> FreeSpaceManager<'a> {
+    pub fn new(pool: &'a mut BufferPool) -> Self {
+        FreeSpaceManager { pool }
+    }
+
+    /// Pops the head of the free list if there is one; otherwise grows
+    /// the file by one page past the header, bumping `page_count`. A
+    /// freshly grown page is materialized on disk as all zeroes right
+    /// away, so the very next cache miss on it (`BufferPool::fetch_page`
+    /// calling `DbFile::read_page`) finds a real page to read instead of
+    /// running past end-of-file.
+    pub fn allocate_page(&mut self, header: &mut DBHeader) -> Result<u64> {
+        if header.free_list_head == NIL_PAGE {
+            let page_id = header.page_count;
+            header.page_count += 1;
+            self.pool
+                .file()
+                .write_page(page_id as usize, vec![0u8; PAGE_BUDGET].into_boxed_slice(), 0)?;
+            return Ok(page_id);
+        }
+
+        let page_id = header.free_list_head;
+        let slot = self.pool.fetch_page(page_id as usize)?;
+        let next_free = u64::from_le_bytes(self.pool.frame(slot).as_bytes()[..8].try_into().unwrap());
+        self.pool.unpin_page(page_id as usize, false)?;
+
+        header.free_list_head = next_free;
+        Ok(page_id)
+    }
+
+    /// Pushes `page_id` onto the head of the free list.
+    pub fn free_page(&mut self, header: &mut DBHeader, page_id: u64) -> Result<()> {
+        let slot = self.pool.fetch_page(page_id as usize)?;
+        let frame = self.pool.frame_mut(slot);
+        frame.as_bytes_mut()[..8].copy_from_slice(&header.free_list_head.to_le_bytes());
+        frame.as_bytes_mut()[8..].fill(0);
+        self.pool.unpin_page(page_id as usize, true)?;
+
+        header.free_list_head = page_id;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::fs::OpenOptions;
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::io::file::DbFile;
+    use crate::io::page_codec::Codec;
+
+    fn temp_pool(name: &str) -> (BufferPool, std::path::PathBuf) {
+        let path = std::env::temp_dir().join(format!(
+            "burqdb-freespace-test-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .write(true)
+            .truncate(true)
+            .open(&path)
+            .unwrap();
+        let db_file = DbFile::new(Rc::new(RefCell::new(file)), false, Codec::None);
+        (BufferPool::new(db_file, 8, PAGE_BUDGET), path)
+    }
+
+    #[test]
+    fn allocate_page_grows_the_file_and_materializes_a_readable_page() {
+        let (mut pool, path) = temp_pool("grow");
+        let mut header = DBHeader::new(PAGE_BUDGET as u32);
+
+        let first = FreeSpaceManager::new(&mut pool).allocate_page(&mut header).unwrap();
+        let second = FreeSpaceManager::new(&mut pool).allocate_page(&mut header).unwrap();
+
+        assert_eq!(first, 0);
+        assert_eq!(second, 1);
+        assert_eq!(header.page_count, 2);
+
+        // The freshly grown page must already be readable through the
+        // pool, not just past-EOF garbage.
+        let slot = pool.fetch_page(1).unwrap();
+        assert_eq!(pool.frame(slot).as_bytes(), &[0u8; PAGE_BUDGET][..]);
+        pool.unpin_page(1, false).unwrap();
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn freed_pages_are_reused_before_the_file_grows_again() {
+        let (mut pool, path) = temp_pool("reuse");
+        let mut header = DBHeader::new(PAGE_BUDGET as u32);
+
+        let a = FreeSpaceManager::new(&mut pool).allocate_page(&mut header).unwrap();
+        let b = FreeSpaceManager::new(&mut pool).allocate_page(&mut header).unwrap();
+        assert_eq!(header.page_count, 2);
+
+        FreeSpaceManager::new(&mut pool).free_page(&mut header, a).unwrap();
+        assert_eq!(header.free_list_head, a);
+
+        let reused = FreeSpaceManager::new(&mut pool).allocate_page(&mut header).unwrap();
+        assert_eq!(reused, a);
+        assert_eq!(header.free_list_head, NIL_PAGE);
+        // No new page was materialized — the free list satisfied this
+        // allocation instead of growing the file.
+        assert_eq!(header.page_count, 2);
+
+        let grown_again = FreeSpaceManager::new(&mut pool).allocate_page(&mut header).unwrap();
+        assert_ne!(grown_again, b);
+        assert_eq!(header.page_count, 3);
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn free_list_threads_multiple_freed_pages_in_lifo_order() {
+        let (mut pool, path) = temp_pool("lifo");
+        let mut header = DBHeader::new(PAGE_BUDGET as u32);
+
+        let a = FreeSpaceManager::new(&mut pool).allocate_page(&mut header).unwrap();
+        let b = FreeSpaceManager::new(&mut pool).allocate_page(&mut header).unwrap();
+
+        FreeSpaceManager::new(&mut pool).free_page(&mut header, a).unwrap();
+        FreeSpaceManager::new(&mut pool).free_page(&mut header, b).unwrap();
+
+        let first_reused = FreeSpaceManager::new(&mut pool).allocate_page(&mut header).unwrap();
+        let second_reused = FreeSpaceManager::new(&mut pool).allocate_page(&mut header).unwrap();
+
+        assert_eq!(first_reused, b);
+        assert_eq!(second_reused, a);
+        assert_eq!(header.free_list_head, NIL_PAGE);
+
+        let _ = std::fs::remove_file(path);
+    }
+}
@@ -1,343 +1,311 @@
-// use std::collections::HashMap;
-
-// use anyhow::Result;
-// use serde::{self, Deserialize, Serialize};
-
-// use crate::storage::cell::Cell;
-
-// static PAGE_SIZE: u16 = 4095;
-
-// //Currently let's keep it simple with only one page type later we can implement index pages
-// #[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
-// pub enum PageType {
-//     Leaf,
-//     Internal,
-// }
-
-// #[derive(Serialize, Deserialize)]
-// pub enum Position {
-//     Free(u16),
-//     Occupied(u16),
-// }
-
-// #[derive(Serialize, Deserialize)]
-// pub struct PageHeader<K, V> {
-//     page_type: PageType,
-//     page_id: u64,
-//     right: Option<Box<SlottedPage<K, V>>>,
-//     page_size: u16,
-//     offset: u16,
-//     first_freeblock: u16,
-// }
-
-
-// impl<K, V> PageHeader<K, V>
-// where
-//     K: Serialize + for<'de> Deserialize<'de> + Ord,
-//     V: Serialize + for<'de> Deserialize<'de>,
-// {
-//     pub fn new(page_id: u64, right: Option<Box<SlottedPage<K, V>>>, page_type: PageType) -> Self {
-//         Self {
-//             page_type,
-//             page_id,
-//             right,
-//             page_size: 0,
-//             offset: PAGE_SIZE as u16,
-//             first_freeblock: 0,
-//         }
-//     }
-// }
-
-// #[derive(Clone, Copy, Serialize, Deserialize, Eq, PartialEq)]
-// pub struct FreeBlock {
-//     offset: u16,
-//     size: u16,
-// }
-
-// impl FreeBlock {
-//     pub fn split(&mut self, size: u16) {}
-// }
-
-// #[derive(Serialize, Deserialize)]
-// pub struct SlottedPage<K, V> {
-//     header: PageHeader<K, V>,
-//     offsets: Vec<usize>,
-//     cells: HashMap<u16, Cell<K, V>>,
-//     freeblocks: Vec<FreeBlock>,
-// }
-
-// impl<K, V> SlottedPage<K, V>
-// where
-//     K: Serialize + for<'de> Deserialize<'de> + Ord,
-//     V: Serialize + for<'de> Deserialize<'de>,
-// {
-//     pub fn new(
-//         page_id: u64,
-//         right: Option<Box<SlottedPage<K, V>>>,
-//         page_type: PageType,
-//     ) -> Result<Self> {
-//         Ok(Self {
-//             header: PageHeader::new(page_id, right, page_type),
-//             offsets: Vec::new(),
-//             cells: HashMap::new(),
-//             freeblocks: Vec::new(),
-//         })
-//     }
-
-//     pub fn page_id(&self) -> u64 {
-//         self.header.page_id
-//     }
-
-//     pub fn can_insert(&mut self, cell: &mut Cell<K, V>) -> Result<Position> {
-//         let required_bytes = cell.size() as u16;
-//         let header_side =
-//             size_of::<PageHeader<K, V>>() as u16 + (self.offsets.len() as u16 + 1) * 2;
-
-//         if self.header.offset - header_side >= required_bytes + 2 {
-//             Ok(Position::Free(required_bytes as u16))
-//         } else {
-//             let mut freeblock;
-//             {
-//                 freeblock = self
-//                     .freeblocks
-//                     .iter()
-//                     .enumerate()
-//                     .find(|(_, block)| required_bytes <= block.size)
-//                     .and_then(f);
-//             }
-//             if let Some((index, block)) = freeblock {
-//                 let remaining_bytes = block.size - required_bytes;
-
-//                 if remaining_bytes.ge(&4) {
-//                     self.freeblocks[index].size = remaining_bytes;
-//                 }
-
-//                 Ok(Position::Occupied(required_bytes - block.offset))
-//             } else {
-//                 Err(anyhow::anyhow!("Not enough space to insert the cell"))
-//             }
-//         }
-//     }
-
-//     pub fn remove(&mut self, key: &K) -> Result<()> {
-//         match self.find_key_index(key) {
-//             Some(mut cell) => {
-//                 let offset = self.offsets.remove(cell as usize);
-//                 let mut cell = self.cells.remove(&(offset as u16)).unwrap();
-//                 let size = cell.size() as usize;
-//                 self.free_list.push(size);
-//                 self.header.page_size -= size as u16;
-//                 self.header.page_size -= 1;
-//                 Ok(())
-//             }
-//             None => anyhow::bail!("No such key found"),
-//         }
-//     }
-
-//     pub fn insert(&mut self, key: K, value: V) -> Result<()> {
-//         let mut cell = Cell::new(key, value)?;
-//         match self.can_insert(&mut cell) {
-//             Ok(pos) => match pos {
-//                 Position::Free(size) => {
-//                     self.header.offset -= size + 1;
-//                     let offset = self.header.offset + 1;
-//                     self.header.page_size += size;
-//                     self.offsets.push(offset as usize);
-//                     self.cells.insert(offset, cell);
-//                 }
-//                 Position::Occupied(at) => {
-//                     let index = self
-//                         .free_list
-//                         .iter()
-//                         .position(|&s| s == at as usize)
-//                         .unwrap();
-//                     self.free_list.remove(index);
-//                     self.header.page_size += at;
-//                     self.offsets.push(at as usize);
-//                     self.cells.insert(at, cell);
-//                 }
-//             },
-//             Err(_) => anyhow::bail!("Not enough space to insert the cell"),
-//         }
-
-//         self.header.page_size += 1;
-//         self.offsets.sort_by(|a, b| {
-//             let cell_a = self.cells.get(&(*a as u16)).unwrap();
-//             let cell_b = self.cells.get(&(*b as u16)).unwrap();
-//             cell_a.key.cmp(&cell_b.key)
-//         });
-//         Ok(())
-//     }
-
-//     // kind of upper bound, return less than key suppose if cells are 1,3,5,6 if 2 is searched it should return 1
-//     pub fn find_key(&self, key: &K) -> Option<&Cell<K, V>> {
-//         let mut left = 0u16;
-//         let mut right = self.offsets.len() as u16 - 1;
-//         let mut result: Option<&Cell<K, V>> = None;
-
-//         while left <= right {
-//             let mid = (left + right) / 2;
-//             let cell = self
-//                 .cells
-//                 .get(&(self.offsets[mid as usize] as u16))
-//                 .unwrap();
-//             if &cell.key == key {
-//                 return Some(cell);
-//             } else if &cell.key < key {
-//                 result = Some(cell);
-//                 left = mid + 1;
-//             } else {
-//                 right = mid - 1;
-//             }
-//         }
-
-//         match result {
-//             Some(cell) => Some(cell),
-//             None => None,
-//         }
-//     }
-
-//     pub fn find_key_index(&self, key: &K) -> Option<u16> {
-//         let mut left = 0;
-//         let mut right = self.offsets.len() as i32 - 1;
-
-//         while left <= right {
-//             let mid = (left + right) / 2;
-//             let cell = self
-//                 .cells
-//                 .get(&(self.offsets[mid as usize] as u16))
-//                 .unwrap();
-//             if &cell.key == key {
-//                 return Some(mid as u16);
-//             } else if &cell.key < key {
-//                 left = mid + 1;
-//             } else {
-//                 right = mid - 1;
-//             }
-//         }
-//         None
-//     }
-// }
-
-// #[cfg(test)]
-// mod tests {
-
-//     use super::*;
-
-//     #[test]
-//     fn test_cell_serialization() {
-//         let cell = Cell::new("key1".to_string(), "value1".to_string()).unwrap();
-//         let serialized = cell._serialize().unwrap();
-//         assert!(!serialized.is_empty());
-//     }
-
-//     #[test]
-//     fn test_cell_size() {
-//         let mut cell: Cell<String, String> =
-//             Cell::new("key12".to_string(), "value1".to_string()).unwrap();
-//         assert_eq!(cell.size(), 13);
-//     }
-
-//     #[test]
-//     fn test_cell_size_int() {
-//         let mut cell: Cell<i32, i32> = Cell::new(1, 1).unwrap();
-//         assert_eq!(cell.size(), 2);
-//     }
-//     #[test]
-//     fn test_add_cell() {
-//         let mut page = SlottedPage::new(0, None, PageType::Internal).unwrap();
-//         let result = page.insert("key1".to_string(), "value1".to_string());
-//         assert!(result.is_ok());
-//     }
-
-//     #[test]
-//     fn test_find_cell() {
-//         let mut page = SlottedPage::new(0, None, PageType::Internal).unwrap();
-//         page.insert("key1".to_string(), "value1".to_string())
-//             .unwrap();
-//         let cell = page.find_key(&"key1".to_string());
-//         assert!(cell.is_some());
-//     }
-
-//     #[test]
-//     fn test_multiple_cells() {
-//         let mut page = SlottedPage::new(0, None, PageType::Internal).unwrap();
-//         page.insert("key1".to_string(), "value1".to_string())
-//             .unwrap();
-//         page.insert("key2".to_string(), "value2".to_string())
-//             .unwrap();
-//         page.insert("key3".to_string(), "value3".to_string())
-//             .unwrap();
-
-//         let cell1 = page.find_key(&"key1".to_string());
-//         let cell2 = page.find_key(&"key2".to_string());
-//         let cell3 = page.find_key(&"key3".to_string());
-
-//         assert!(cell1.is_some());
-//         assert!(cell2.is_some());
-//         assert!(cell3.is_some());
-//     }
-
-//     #[test]
-//     fn test_offset_order() {
-//         println!("test offset order");
-//         let mut page = SlottedPage::new(0, None, PageType::Internal).unwrap();
-//         page.insert("b".to_string(), "value1".to_string()).unwrap();
-//         page.insert("c".to_string(), "value2".to_string()).unwrap();
-//         page.insert("a".to_string(), "value3".to_string()).unwrap();
-
-//         let s = format!("{:?}", page.offsets.clone());
-//         println!("{}", s);
-//         let offsets = page.offsets.clone();
-//         assert_eq!(offsets, vec![4066, 4086, 4076]);
-//     }
-
-//     #[test]
-//     fn test_full_page() {
-//         let mut page = SlottedPage::new(0, None, PageType::Internal).unwrap();
-//         let mut index = 0;
-//         let mut error = anyhow::anyhow!("No error");
-//         while match page.insert(format!("key{}", index), format!("value{}", index)) {
-//             Ok(_) => {
-//                 index += 1;
-//                 true
-//             }
-//             Err(err) => {
-//                 error = err;
-//                 false
-//             }
-//         } {}
-//         assert!(
-//             error
-//                 .to_string()
-//                 .contains("Not enough space to insert the cell")
-//         );
-//     }
-
-//     #[test]
-//     fn test_insert_in_freelist() {
-//         let mut page = SlottedPage::new(0, None, PageType::Internal).unwrap();
-//         let mut index = 0;
-//         let mut error = anyhow::anyhow!("No error");
-//         while match page.insert(format!("key{}", index), format!("value{}", index)) {
-//             Ok(_) => {
-//                 index += 1;
-//                 true
-//             }
-//             Err(err) => {
-//                 error = err;
-//                 false
-//             }
-//         } {}
-
-//         let delete_cells = vec!["key11", "key10", "key4", "key3", "key2"];
-//         for key in delete_cells {
-//             assert!(page.remove(&key.to_string()).is_ok());
-//         }
-
-//         assert_eq!(page.free_list.len(), 5);
-//         assert_eq!(page.free_list.iter().sum::<usize>(), 27);
-//         assert_eq!(page.header.page_size, 3870);
-//         assert_eq!(page.offsets.len(), index - 5);
-//     }
-// }
+//! # SlottedPage
+//!
+//! In-memory representation of the two kinds of B-tree node page.
+//! `SlottedPage<K, V>` is a leaf: its cells are kept in a single vector
+//! sorted by key rather than the classic slot-directory-plus-freeblock
+//! layout, since burqdb pages are (de)serialized whole through `DbFile`
+//! rather than mutated in place on disk. `right` threads leaves into a
+//! singly linked list so `BTree::range_query` can walk sideways without
+//! returning to the root. `IndexPage<K>` is an internal node: a sorted
+//! list of `(separator_key, child_page_id)` pairs plus `leftmost_child`
+//! for keys smaller than every separator. `Node` is the tagged union of
+//! the two that actually gets written to and read from a page slot.
+
+use anyhow::{bail, Result};
+use bincode::{
+    config::standard,
+    serde::{decode_from_slice, encode_to_vec},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::io::file::PAGE_BUDGET;
+use crate::storage::cell::Cell;
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SlottedPage<K, V> {
+    pub page_id: u64,
+    pub right: Option<u64>,
+    cells: Vec<Cell<K, V>>,
+}
+
+impl<K, V> SlottedPage<K, V>
+where
+    K: Serialize + for<'de> Deserialize<'de> + Ord + Clone,
+    V: Serialize + for<'de> Deserialize<'de> + Clone,
+{
+    pub fn new(page_id: u64) -> Self {
+        SlottedPage {
+            page_id,
+            right: None,
+            cells: Vec::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.cells.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+
+    pub fn cells(&self) -> &[Cell<K, V>] {
+        &self.cells
+    }
+
+    fn index_of(&self, key: &K) -> std::result::Result<usize, usize> {
+        self.cells.binary_search_by(|cell| cell.key().cmp(key))
+    }
+
+    pub fn find(&self, key: &K) -> Option<&Cell<K, V>> {
+        self.index_of(key).ok().map(|index| &self.cells[index])
+    }
+
+    /// Inserts `cell`, or replaces the existing cell for its key,
+    /// keeping `cells` sorted.
+    pub fn insert(&mut self, cell: Cell<K, V>) {
+        match self.index_of(cell.key()) {
+            Ok(index) => self.cells[index] = cell,
+            Err(index) => self.cells.insert(index, cell),
+        }
+    }
+
+    pub fn remove(&mut self, key: &K) -> Option<Cell<K, V>> {
+        self.index_of(key)
+            .ok()
+            .map(|index| self.cells.remove(index))
+    }
+
+    /// Moves the upper half of `cells` into a new right sibling page and
+    /// fixes up the leaf chain, returning the sibling along with its
+    /// first key (the separator to push into the parent).
+    pub fn split(&mut self, new_page_id: u64) -> (Self, K) {
+        let mid = self.cells.len() / 2;
+        let upper = self.cells.split_off(mid);
+        let separator_key = upper[0].key().clone();
+
+        let sibling = SlottedPage {
+            page_id: new_page_id,
+            right: self.right,
+            cells: upper,
+        };
+        self.right = Some(new_page_id);
+
+        (sibling, separator_key)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct IndexPage<K> {
+    pub page_id: u64,
+    pub leftmost_child: u64,
+    separators: Vec<(K, u64)>,
+}
+
+impl<K> IndexPage<K>
+where
+    K: Serialize + for<'de> Deserialize<'de> + Ord + Clone,
+{
+    pub fn new(page_id: u64, leftmost_child: u64) -> Self {
+        IndexPage {
+            page_id,
+            leftmost_child,
+            separators: Vec::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.separators.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.separators.is_empty()
+    }
+
+    /// The child to descend into to find `key`: the child of the last
+    /// separator that is `<= key`, or `leftmost_child` if `key` is
+    /// smaller than every separator.
+    pub fn child_for(&self, key: &K) -> u64 {
+        match self.separators.binary_search_by(|(sep, _)| sep.cmp(key)) {
+            Ok(index) => self.separators[index].1,
+            Err(0) => self.leftmost_child,
+            Err(index) => self.separators[index - 1].1,
+        }
+    }
+
+    pub fn insert(&mut self, key: K, child_page_id: u64) {
+        match self.separators.binary_search_by(|(sep, _)| sep.cmp(&key)) {
+            Ok(index) => self.separators[index] = (key, child_page_id),
+            Err(index) => self.separators.insert(index, (key, child_page_id)),
+        }
+    }
+
+    /// Removes the entry routing to `child_page_id`, so the page it
+    /// names is no longer reachable from this node. If it was
+    /// `leftmost_child`, the next separator's child is promoted into
+    /// that slot and its now-redundant separator key is dropped instead;
+    /// if there is no separator to promote, nothing changes and
+    /// `leftmost_child` still routes to it.
+    pub fn remove_child(&mut self, child_page_id: u64) {
+        if self.leftmost_child == child_page_id {
+            if !self.separators.is_empty() {
+                let (_, promoted_child) = self.separators.remove(0);
+                self.leftmost_child = promoted_child;
+            }
+            return;
+        }
+
+        if let Some(index) = self
+            .separators
+            .iter()
+            .position(|(_, child)| *child == child_page_id)
+        {
+            self.separators.remove(index);
+        }
+    }
+
+    /// Moves the upper half of the separators into a new page. Unlike a
+    /// leaf split, the middle separator is removed from both halves and
+    /// promoted to the grandparent, becoming the new page's
+    /// `leftmost_child`.
+    pub fn split(&mut self, new_page_id: u64) -> (Self, K) {
+        let mid = self.separators.len() / 2;
+        let mut upper = self.separators.split_off(mid);
+        let (promoted_key, promoted_child) = upper.remove(0);
+
+        let sibling = IndexPage {
+            page_id: new_page_id,
+            leftmost_child: promoted_child,
+            separators: upper,
+        };
+
+        (sibling, promoted_key)
+    }
+}
+
+/// The tagged union actually (de)serialized through `DbFile`; the enum
+/// discriminant is what tells `BTree` whether a page it just read is a
+/// leaf or an internal node.
+#[derive(Serialize, Deserialize, Clone)]
+pub enum Node<K, V> {
+    Leaf(SlottedPage<K, V>),
+    Internal(IndexPage<K>),
+}
+
+impl<K, V> Node<K, V>
+where
+    K: Serialize + for<'de> Deserialize<'de> + Ord + Clone,
+    V: Serialize + for<'de> Deserialize<'de> + Clone,
+{
+    pub fn page_id(&self) -> u64 {
+        match self {
+            Node::Leaf(page) => page.page_id,
+            Node::Internal(page) => page.page_id,
+        }
+    }
+
+    /// Whether this node, as it currently stands, still fits in a page.
+    pub fn fits(&self) -> Result<bool> {
+        Ok(encode_to_vec(self, standard())?.len() <= PAGE_BUDGET)
+    }
+
+    pub fn to_bytes(&self) -> Result<Box<[u8]>> {
+        let mut bytes = encode_to_vec(self, standard())?;
+        if bytes.len() > PAGE_BUDGET {
+            bail!(
+                "page {} needs {} bytes, which does not fit in the {} byte budget",
+                self.page_id(),
+                bytes.len(),
+                PAGE_BUDGET
+            );
+        }
+        bytes.resize(PAGE_BUDGET, 0);
+        Ok(bytes.into_boxed_slice())
+    }
+
+    pub fn from_bytes(buffer: &[u8]) -> Result<Self> {
+        let (node, _) = decode_from_slice(buffer, standard())?;
+        Ok(node)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cell(key: &str, value: &str) -> Cell<String, String> {
+        Cell::new(key.to_string(), value.to_string()).unwrap()
+    }
+
+    #[test]
+    fn slotted_page_insert_keeps_cells_sorted_and_replaces_duplicates() {
+        let mut page: SlottedPage<String, String> = SlottedPage::new(0);
+        page.insert(cell("b", "2"));
+        page.insert(cell("a", "1"));
+        page.insert(cell("c", "3"));
+        page.insert(cell("a", "1-updated"));
+
+        let keys: Vec<&String> = page.cells().iter().map(|c| c.key()).collect();
+        assert_eq!(keys, vec!["a", "b", "c"]);
+        assert_eq!(page.find(&"a".to_string()).unwrap().value(), "1-updated");
+    }
+
+    #[test]
+    fn slotted_page_split_moves_upper_half_and_threads_right_pointer() {
+        let mut page: SlottedPage<String, String> = SlottedPage::new(0);
+        for key in ["a", "b", "c", "d"] {
+            page.insert(cell(key, key));
+        }
+
+        let (sibling, separator_key) = page.split(1);
+
+        assert_eq!(page.len(), 2);
+        assert_eq!(sibling.len(), 2);
+        assert_eq!(separator_key, "c");
+        assert_eq!(page.right, Some(1));
+        assert!(page.find(&"c".to_string()).is_none());
+        assert!(sibling.find(&"c".to_string()).is_some());
+    }
+
+    #[test]
+    fn index_page_child_for_routes_to_the_last_separator_not_exceeding_key() {
+        let mut page: IndexPage<String> = IndexPage::new(0, 100);
+        page.insert("m".to_string(), 200);
+        page.insert("t".to_string(), 300);
+
+        assert_eq!(page.child_for(&"a".to_string()), 100);
+        assert_eq!(page.child_for(&"m".to_string()), 200);
+        assert_eq!(page.child_for(&"n".to_string()), 200);
+        assert_eq!(page.child_for(&"z".to_string()), 300);
+    }
+
+    #[test]
+    fn index_page_remove_child_promotes_leftmost_when_needed() {
+        let mut page: IndexPage<String> = IndexPage::new(0, 100);
+        page.insert("m".to_string(), 200);
+        page.insert("t".to_string(), 300);
+
+        page.remove_child(100);
+        assert_eq!(page.leftmost_child, 200);
+        assert_eq!(page.child_for(&"a".to_string()), 200);
+
+        page.remove_child(300);
+        assert_eq!(page.child_for(&"z".to_string()), 200);
+    }
+
+    #[test]
+    fn node_round_trips_through_bytes() {
+        let mut leaf: SlottedPage<String, String> = SlottedPage::new(0);
+        leaf.insert(cell("a", "1"));
+        let node = Node::Leaf(leaf);
+
+        let bytes = node.to_bytes().unwrap();
+        assert_eq!(bytes.len(), PAGE_BUDGET);
+
+        let decoded: Node<String, String> = Node::from_bytes(&bytes).unwrap();
+        match decoded {
+            Node::Leaf(page) => assert_eq!(page.find(&"a".to_string()).unwrap().value(), "1"),
+            Node::Internal(_) => panic!("expected a leaf"),
+        }
+    }
+}